@@ -3,18 +3,19 @@
 use codec::{Encode, Decode};
 use frame_support::{
 	decl_module, decl_storage, decl_event, decl_error, ensure, StorageValue, StorageDoubleMap, Parameter,
-	traits::Randomness, RuntimeDebug, dispatch::{DispatchError, DispatchResult}, fail
+	traits::Randomness, RuntimeDebug, dispatch::{DispatchError, DispatchResult}, weights::Weight
 };
 use sp_io::hashing::blake2_128;
-use frame_system::ensure_signed;
-use frame_support::traits::{Currency};
+use frame_system::{ensure_signed, ensure_root};
+use frame_support::traits::{Currency, ExistenceRequirement, Get};
 use sp_runtime::traits::{AtLeast32BitUnsigned, Bounded, One, CheckedAdd};
+use sp_std::vec::Vec;
 
 #[cfg(test)]
 mod tests;
 
 #[derive(Encode, Decode, Clone, RuntimeDebug, PartialEq, Eq)]
-pub struct Kitty(pub [u8; 16]);
+pub struct Kitty(pub [u8; 16], pub u32);
 
 #[derive(Encode, Decode, Clone, Copy, RuntimeDebug, PartialEq, Eq)]
 pub enum KittyGender {
@@ -23,6 +24,12 @@ pub enum KittyGender {
 }
 
 impl Kitty {
+	/// The generation a kitty belongs to; freshly created kitties are `0` and a
+	/// bred kitty is one beyond the higher generation of its two parents.
+	pub fn generation(&self) -> u32 {
+		self.1
+	}
+
 	pub fn gender(&self) -> KittyGender {
 		if self.0[0] % 2 == 0 {
 			KittyGender::Male
@@ -32,11 +39,34 @@ impl Kitty {
 	}
 }
 
+/// The payload carried to another chain when a kitty is bridged out.
+#[derive(Encode, Decode, Clone, RuntimeDebug, PartialEq, Eq)]
+pub struct KittyTransfer<KittyIndex> {
+	/// The opaque account address on the destination chain.
+	pub dest: Vec<u8>,
+	pub kitty_id: KittyIndex,
+	pub dna: [u8; 16],
+	pub generation: u32,
+}
+
+/// Sink for outbound bridge messages, letting a runtime wire the pallet to XCM
+/// or an off-chain relayer. The pallet pushes each encoded payload under a topic.
+pub trait MessageSink {
+	fn push(topic: &[u8], bytes: &[u8]);
+}
+
+/// A no-op sink, convenient for runtimes that do not yet bridge and for tests.
+impl MessageSink for () {
+	fn push(_topic: &[u8], _bytes: &[u8]) {}
+}
+
 pub trait Config: frame_system::Config {
 	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
 	type Randomness: Randomness<Self::Hash>;
 	type KittyIndex: Parameter + AtLeast32BitUnsigned + Bounded + Default + Copy;
 	type Currency: Currency<Self::AccountId>;
+	/// Where bridged-out kitty messages are delivered.
+	type MessageSink: MessageSink;
 }
 
 type KittyIndexOf<T> = <T as Config>::KittyIndex;
@@ -51,8 +81,24 @@ decl_storage! {
 		pub NextKittyId get(fn next_kitty_id): T::KittyIndex;
 		//Kitty prices are set as options against KittyId  because a price may not be set for a kitty, meeaning that it is not up for sale
 		pub KittyPrice get(fn kitty_price): map hasher(blake2_128_concat) KittyIndexOf<T> => Option<BalanceOf<T>>;
-
-		pub Balance get(fn balance): map hasher(blake2_128_concat) T::AccountId => Option<BalanceOf<T>>;
+		/// A monotonically increasing counter mixed into every randomness draw so
+		/// that successive draws in the same block do not collide.
+		pub Nonce get(fn nonce): u32;
+		/// The set of DNAs of kitties living on this chain, used to reject collisions.
+		/// A bridged-out kitty's DNA is removed again.
+		pub KittyDnas get(fn kitty_dnas): map hasher(blake2_128_concat) [u8; 16] => ();
+		/// Total number of kitties in existence.
+		pub AllKittiesCount get(fn all_kitties_count): u32;
+		/// Number of kitties each account owns.
+		pub OwnedKittiesCount get(fn owned_kitties_count): map hasher(blake2_128_concat) T::AccountId => u32;
+		/// Per-owner contiguous `0..count` list, mapping a slot to the kitty held there.
+		pub OwnedKittiesIndex get(fn owned_kitties_index): double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) u32 => T::KittyIndex;
+		/// Reverse of `OwnedKittiesIndex`: the slot a given kitty occupies in its owner's list.
+		pub OwnedKittiesPosition get(fn owned_kitties_position): double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) T::KittyIndex => u32;
+		/// Pending outbound bridge messages as `(topic, encoded payload)` pairs.
+		pub OutboundMessages get(fn outbound_messages): Vec<(Vec<u8>, Vec<u8>)>;
+		/// On-chain storage layout version, bumped by migrations so each runs once.
+		pub StorageVersion get(fn storage_version): u16;
 	}
 }
 
@@ -64,13 +110,17 @@ decl_event! {
 	{
 		/// A kitty is created. \[owner, kitty_id, kitty\]
 		KittyCreated(AccountId, KittyIndex, Kitty),
-		/// A new kitten is bred. \[owner, kitty_id, kitty\]
-		KittyBred(AccountId, KittyIndex, Kitty),
+		/// A new kitten is bred. \[owner, kitty_id, kitty, generation\]
+		KittyBred(AccountId, KittyIndex, Kitty, u32),
 		/// A kitty is transferred. \[from, to, kitty_id\]
 		KittyTransferred(AccountId, AccountId, KittyIndex),
+		/// A kitty is listed for sale. \[kitty_id, price\]
 		KittyPriceSet(KittyIndex, Balance),
-		KittyPrice(KittyIndex, Balance),
+		/// A kitty is delisted and is no longer for sale. \[kitty_id\]
+		KittyPriceUnset(KittyIndex),
 		KittyExchanged(KittyIndex, AccountId, AccountId),
+		/// A kitty was burned and sent to another chain. \[from, kitty_id, dest_chain\]
+		KittyBridged(AccountId, KittyIndex, Vec<u8>),
 	}
 }
 
@@ -81,7 +131,9 @@ decl_error! {
 		SameGender,
 		NotForSale,
 		CannotExchangeWithYourself,
-		NotEnoughBalance
+		DnaCollision,
+		KittiesCountOverflow,
+		KittiesCountUnderflow,
 	}
 }
 
@@ -91,6 +143,21 @@ decl_module! {
 
 		fn deposit_event() = default;
 
+		/// Re-encode the `Kitties` storage after the `Kitty` layout gained a
+		/// `generation` field; pre-existing kitties are treated as generation 0.
+		/// Gated on `StorageVersion` so it runs exactly once — re-decoding the new
+		/// 20-byte layout as the old 16-byte one would silently wipe every
+		/// generation back to zero.
+		fn on_runtime_upgrade() -> Weight {
+			if StorageVersion::get() < 1 {
+				let weight = migration::migrate_to_generation::<T>();
+				StorageVersion::put(1);
+				weight
+			} else {
+				0
+			}
+		}
+
 		/// Create a new kitty
 		#[weight = 1000]
 		pub fn create(origin) {
@@ -98,11 +165,20 @@ decl_module! {
 
 			let kitty_id = Self::get_next_kitty_id()?;
 
-			let dna = Self::random_value(&sender);
+			// Draw a globally-unique DNA, re-rolling on collision.
+			let mut dna = Self::random_value(&sender);
+			let mut tries = 0;
+			while KittyDnas::contains_key(&dna) {
+				tries += 1;
+				ensure!(tries < MAX_DNA_REROLLS, Error::<T>::DnaCollision);
+				dna = Self::random_value(&sender);
+			}
 
-			// Create and store kitty
-			let kitty = Kitty(dna);
+			// Create and store kitty; freshly minted kitties are generation 0.
+			let kitty = Kitty(dna, 0);
 			Kitties::<T>::insert(&sender, kitty_id, &kitty);
+			KittyDnas::insert(dna, ());
+			Self::mint_bookkeeping(&sender, kitty_id)?;
 
 			// Emit event
 			Self::deposit_event(RawEvent::KittyCreated(sender, kitty_id, kitty));
@@ -122,19 +198,31 @@ decl_module! {
 			let kitty1_dna = kitty1.0;
 			let kitty2_dna = kitty2.0;
 
-			let selector = Self::random_value(&sender);
-			let mut new_dna = [0u8; 16];
-
-			// Combine parents and selector to create new kitty
-			for i in 0..kitty1_dna.len() {
-				new_dna[i] = combine_dna(kitty1_dna[i], kitty2_dna[i], selector[i]);
-			}
+			// Combine parents and a fresh selector to create new kitty, re-rolling
+			// the selector should the resulting DNA already exist.
+			let mut tries = 0;
+			let new_dna = loop {
+				let selector = Self::random_value(&sender);
+				let mut candidate = [0u8; 16];
+				for i in 0..kitty1_dna.len() {
+					candidate[i] = combine_dna(kitty1_dna[i], kitty2_dna[i], selector[i]);
+				}
+				if !KittyDnas::contains_key(&candidate) {
+					break candidate;
+				}
+				tries += 1;
+				ensure!(tries < MAX_DNA_REROLLS, Error::<T>::DnaCollision);
+			};
 
-			let new_kitty = Kitty(new_dna);
+			// The child sits one generation beyond the higher-generation parent.
+			let generation = kitty1.generation().max(kitty2.generation()) + 1;
+			let new_kitty = Kitty(new_dna, generation);
 
 			Kitties::<T>::insert(&sender, kitty_id, &new_kitty);
+			KittyDnas::insert(new_dna, ());
+			Self::mint_bookkeeping(&sender, kitty_id)?;
 
-			Self::deposit_event(RawEvent::KittyBred(sender, kitty_id, new_kitty));
+			Self::deposit_event(RawEvent::KittyBred(sender, kitty_id, new_kitty, generation));
 		}
 
 		/// Transfer a kitty to new owner
@@ -152,68 +240,159 @@ decl_module! {
 
 				Kitties::<T>::insert(&to, kitty_id, kitty);
 
+				Self::remove_kitty_from_owner(&sender, kitty_id)?;
+				Self::add_kitty_to_owner(&to, kitty_id)?;
+
+				// A received kitty is never inadvertently still for sale.
+				KittyPrice::<T>::remove(kitty_id);
+
 				Self::deposit_event(RawEvent::KittyTransferred(sender, to, kitty_id));
 
 				Ok(())
 			})?;
 		}
 		#[weight = 2000]
-		pub fn set_price(origin, kitty_id: KittyIndexOf<T>, new_price: BalanceOf<T>) -> DispatchResult {
+		pub fn set_price(origin, kitty_id: KittyIndexOf<T>, new_price: Option<BalanceOf<T>>) -> DispatchResult {
 			let setter = ensure_signed(origin)?;
 			let is_mine = Kitties::<T>::contains_key(&setter, &kitty_id);
 			ensure!(is_mine, Error::<T>::InvalidKittyId);
-			let price = KittyPrice::<T>::get(kitty_id).ok_or(Error::<T>::NotForSale)?;
-			KittyPrice::<T>::mutate_exists(kitty_id, |price| {*price = Some(new_price)});
-			Self::deposit_event(RawEvent::KittyPriceSet(kitty_id, price));
+			match new_price {
+				// `Some(price)` lists the kitty for sale at `price`.
+				Some(price) => {
+					KittyPrice::<T>::insert(kitty_id, price);
+					Self::deposit_event(RawEvent::KittyPriceSet(kitty_id, price));
+				},
+				// `None` delists the kitty, removing any standing price.
+				None => {
+					KittyPrice::<T>::remove(kitty_id);
+					Self::deposit_event(RawEvent::KittyPriceUnset(kitty_id));
+				},
+			}
 			Ok(())
 		}
 
+		// Transactional so that a failure in the post-transfer bookkeeping rolls
+		// the balance transfer back too — `decl_module` dispatch is not atomic on
+		// its own, and chunk0-1 requires that no funds move without a sale.
 		#[weight = 2000]
-		pub fn exchange(origin, receiver: T::AccountId, kitty_id: KittyIndexOf<T>) -> DispatchResult {
-			let sender = ensure_signed(origin)?;
-			//first we ensure that the sender is not the receiver
-			ensure!(sender != receiver, Error::<T>::CannotExchangeWithYourself);
-			// then we get the sender's balance so that we an chck if they have the reqd amunt
-			let my_balance = Balance::<T>::get(&receiver);
+		#[frame_support::transactional]
+		pub fn exchange(origin, owner: T::AccountId, kitty_id: KittyIndexOf<T>) -> DispatchResult {
+			let buyer = ensure_signed(origin)?;
+			//first we ensure that the buyer is not the current owner
+			ensure!(buyer != owner, Error::<T>::CannotExchangeWithYourself);
+			// the kitty must actually belong to the named owner
+			ensure!(Kitties::<T>::contains_key(&owner, &kitty_id), Error::<T>::InvalidKittyId);
 			//is the kitty for sale?
-			let price = KittyPrice::<T>::take(kitty_id).ok_or(Error::<T>::NotForSale)?;
-			if let Some(money) = my_balance  {
-					if price < money {
-						//let _new_bal = money - price;
-						Balance::<T>::mutate_exists(&sender, |bal| {
-							if let Some(my_bal) = bal {
-								let new_bal = *my_bal - price;
-								*bal = Some(new_bal);
-							}
-							
-						});
-						Balance::<T>::mutate_exists(&receiver, |bal| {
-							if let Some(my_bal) = bal {
-								let new_bal = *my_bal + price;
-								*bal = Some(new_bal);
-							}
-						});
-						Kitties::<T>::take(&sender, &kitty_id).unwrap();
-						Self::deposit_event(RawEvent::KittyExchanged(kitty_id, sender, receiver));
-					} else {
-						// throw an erorr
-						fail!(Error::<T>::NotEnoughBalance);	
-					}
-					Ok(())
-			} else {
-				fail!(Error::<T>::NotForSale);
-			}
+			let price = KittyPrice::<T>::get(kitty_id).ok_or(Error::<T>::NotForSale)?;
 
-			}
+			// Settle in the chain's native token first, before ownership changes
+			// hands; if the count bookkeeping below fails, the call is rolled back.
+			T::Currency::transfer(&buyer, &owner, price, ExistenceRequirement::KeepAlive)?;
+
+			// The ownership check above guarantees the take succeeds.
+			let kitty = Kitties::<T>::take(&owner, &kitty_id).expect("kitty existence checked above; qed");
+			Kitties::<T>::insert(&buyer, &kitty_id, kitty);
+
+			Self::remove_kitty_from_owner(&owner, kitty_id)?;
+			Self::add_kitty_to_owner(&buyer, kitty_id)?;
+
+			// The listing is consumed by the sale; the new owner must relist.
+			KittyPrice::<T>::remove(kitty_id);
+
+			Self::deposit_event(RawEvent::KittyExchanged(kitty_id, buyer, owner));
+			Ok(())
+		}
+
+		/// Send a kitty to another chain: burn it locally and enqueue an outbound
+		/// message on the destination chain's topic for a relayer or XCM to pick up.
+		#[weight = 1000]
+		pub fn bridge_transfer(origin, dest_chain: Vec<u8>, dest_account: Vec<u8>, kitty_id: T::KittyIndex) {
+			let sender = ensure_signed(origin)?;
+			let kitty = Self::kitties(&sender, kitty_id).ok_or(Error::<T>::InvalidKittyId)?;
+
+			// Burn locally before emitting, so the kitty cannot exist on both chains.
+			Kitties::<T>::remove(&sender, kitty_id);
+			KittyPrice::<T>::remove(kitty_id);
+			// Release the DNA too: the kitty no longer lives here, so it must be
+			// allowed to arrive back through `ingest_transfer`.
+			KittyDnas::remove(kitty.0);
+			Self::remove_kitty_from_owner(&sender, kitty_id)?;
+			let all = AllKittiesCount::get().checked_sub(1).ok_or(Error::<T>::KittiesCountUnderflow)?;
+			AllKittiesCount::put(all);
+
+			// Enqueue the structured payload under the destination chain's topic.
+			let payload = KittyTransfer {
+				dest: dest_account,
+				kitty_id,
+				dna: kitty.0,
+				generation: kitty.generation(),
+			};
+			let bytes = payload.encode();
+			OutboundMessages::append((dest_chain.clone(), bytes.clone()));
+			T::MessageSink::push(&dest_chain, &bytes);
+
+			Self::deposit_event(RawEvent::KittyBridged(sender, kitty_id, dest_chain));
+		}
+
+		/// Mint a kitty arriving from another chain. Origin-gated to the trusted
+		/// bridge so only verified inbound messages can create kitties here.
+		#[weight = 1000]
+		pub fn ingest_transfer(origin, to: T::AccountId, dna: [u8; 16], generation: u32) {
+			ensure_root(origin)?;
+			ensure!(!KittyDnas::contains_key(&dna), Error::<T>::DnaCollision);
+
+			let kitty_id = Self::get_next_kitty_id()?;
+			let kitty = Kitty(dna, generation);
+			Kitties::<T>::insert(&to, kitty_id, &kitty);
+			KittyDnas::insert(dna, ());
+			Self::mint_bookkeeping(&to, kitty_id)?;
+
+			Self::deposit_event(RawEvent::KittyCreated(to, kitty_id, kitty));
+		}
 		}
 	
 }
 
 
+/// Maximum number of DNA draws (the first one included) before `create`/`breed`
+/// give up with `DnaCollision`.
+const MAX_DNA_REROLLS: u32 = 10;
+
 fn combine_dna(dna1: u8, dna2: u8, selector: u8) -> u8 {
 	(!selector & dna1) | (selector & dna2)
 }
 
+mod migration {
+	use super::*;
+
+	/// The pre-upgrade shape of a kitty: DNA only, no generation.
+	#[derive(Encode, Decode)]
+	struct OldKitty(pub [u8; 16]);
+
+	/// Rewrite every stored kitty into the new `(dna, generation)` layout,
+	/// assigning generation 0 to everything that already existed, and backfill the
+	/// storage introduced by later requests (the DNA set and the per-owner/global
+	/// enumeration bookkeeping) so pre-existing kitties stay transferable.
+	pub fn migrate_to_generation<T: Config>() -> Weight {
+		let mut count: Weight = 0;
+		let mut all: u32 = 0;
+		Kitties::<T>::translate::<OldKitty, _>(|owner, kitty_id, old| {
+			count += 1;
+			all = all.saturating_add(1);
+			// Register the DNA so collision checks and the bridge see it.
+			KittyDnas::insert(old.0, ());
+			// Append the kitty to the end of its owner's contiguous list.
+			let slot = OwnedKittiesCount::<T>::get(&owner);
+			OwnedKittiesIndex::<T>::insert(&owner, slot, kitty_id);
+			OwnedKittiesPosition::<T>::insert(&owner, kitty_id, slot);
+			OwnedKittiesCount::<T>::insert(&owner, slot.saturating_add(1));
+			Some(Kitty(old.0, 0))
+		});
+		AllKittiesCount::put(all);
+		T::DbWeight::get().reads_writes(count, count)
+	}
+}
+
 impl<T: Config> Module<T> {
 
 	fn get_next_kitty_id() -> sp_std::result::Result<T::KittyIndex, DispatchError> {
@@ -224,12 +403,51 @@ impl<T: Config> Module<T> {
 		})
 	}
 
+	/// Record a freshly minted kitty, bumping both the owner's and the global count.
+	fn mint_bookkeeping(owner: &T::AccountId, kitty_id: T::KittyIndex) -> DispatchResult {
+		let all = AllKittiesCount::get().checked_add(1).ok_or(Error::<T>::KittiesCountOverflow)?;
+		Self::add_kitty_to_owner(owner, kitty_id)?;
+		AllKittiesCount::put(all);
+		Ok(())
+	}
+
+	/// Append a kitty to the end of an owner's contiguous list.
+	fn add_kitty_to_owner(owner: &T::AccountId, kitty_id: T::KittyIndex) -> DispatchResult {
+		let slot = Self::owned_kitties_count(owner);
+		let new_count = slot.checked_add(1).ok_or(Error::<T>::KittiesCountOverflow)?;
+		OwnedKittiesIndex::<T>::insert(owner, slot, kitty_id);
+		OwnedKittiesPosition::<T>::insert(owner, kitty_id, slot);
+		OwnedKittiesCount::<T>::insert(owner, new_count);
+		Ok(())
+	}
+
+	/// Remove a kitty from an owner's list using swap-and-pop to keep it contiguous.
+	fn remove_kitty_from_owner(owner: &T::AccountId, kitty_id: T::KittyIndex) -> DispatchResult {
+		let last_slot = Self::owned_kitties_count(owner)
+			.checked_sub(1)
+			.ok_or(Error::<T>::KittiesCountUnderflow)?;
+		let slot = OwnedKittiesPosition::<T>::take(owner, kitty_id);
+		if slot != last_slot {
+			// Move the last kitty into the freed slot and fix up its position.
+			let moved = OwnedKittiesIndex::<T>::get(owner, last_slot);
+			OwnedKittiesIndex::<T>::insert(owner, slot, moved);
+			OwnedKittiesPosition::<T>::insert(owner, moved, slot);
+		}
+		OwnedKittiesIndex::<T>::remove(owner, last_slot);
+		OwnedKittiesCount::<T>::insert(owner, last_slot);
+		Ok(())
+	}
+
 	fn random_value(sender: &T::AccountId) -> [u8; 16] {
+		let nonce = Nonce::get();
 		let payload = (
 			T::Randomness::random_seed(),
 			&sender,
 			<frame_system::Module<T>>::extrinsic_index(),
+			nonce,
 		);
+		// Bump the nonce so the next draw differs even within the same block.
+		Nonce::mutate(|n| *n = n.wrapping_add(1));
 		payload.using_encoded(blake2_128)
 	}
 }